@@ -0,0 +1,63 @@
+//! Example of a server that negotiates PLAIN vs PUBKEY and authorizes
+//! PUBKEY clients by checking their Ed25519 key against an allow-list,
+//! mirroring SSH's `authorized_keys` lookup.
+//! ```q
+//! q)h:hopen `:unix://4321:homer:j:simpson
+//! ```
+
+use std::io;
+
+use async_trait::async_trait;
+use kxkdb::ipc::*;
+
+struct TestAuth;
+#[async_trait]
+impl Auth for TestAuth {
+    async fn authorize(&mut self, credential: &str) -> Result<()> {
+        if credential.starts_with("homer:") && credential.ends_with(":simpson") {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "authentication failed").into())
+        }
+    }
+}
+
+struct AllowedKeys(Vec<[u8; 32]>);
+#[async_trait]
+impl PubKeyAuth for AllowedKeys {
+    async fn authorize_key(&mut self, public_key: &[u8; 32]) -> Result<()> {
+        if self.0.iter().any(|allowed| allowed == public_key) {
+            Ok(())
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidData, "unknown public key").into())
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut test_auth = TestAuth {};
+    let mut allowed_keys = AllowedKeys(vec![]);
+    if let Ok(mut socket) = QStream::accept_auth_challenge(
+        ConnectionMethod::UDS,
+        "",
+        4321,
+        &mut test_auth,
+        &mut allowed_keys,
+    )
+    .await
+    {
+        loop {
+            match socket.receive_message().await {
+                Ok((_, message)) => {
+                    println!("request: {}", message);
+                }
+                _ => {
+                    socket.shutdown().await.unwrap();
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
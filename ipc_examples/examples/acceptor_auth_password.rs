@@ -0,0 +1,29 @@
+//! Example of a server authorizing clients against an Argon2id credentials
+//! file instead of a hard-coded check.
+//! ```q
+//! q)h:hopen `:unix://4321:homer:donuts
+//! ```
+
+use kxkdb::ipc::*;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    // "credentials.txt" contains lines like `homer:$argon2id$v=19$...`,
+    // produced ahead of time via `PasswordAuth::add_user`.
+    let mut password_auth = PasswordAuth::from_file("credentials.txt")?;
+    // Start listening over UDS at the port 4321
+    if let Ok(mut socket) = QStream::accept_auth(ConnectionMethod::UDS, "", 4321, &mut password_auth).await {
+        loop {
+            match socket.receive_message().await {
+                Ok((_, message)) => {
+                    println!("request: {}", message);
+                }
+                _ => {
+                    socket.shutdown().await.unwrap();
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
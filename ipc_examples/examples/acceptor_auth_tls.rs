@@ -0,0 +1,78 @@
+//! Example of an mTLS server that authorizes clients solely on a pinned
+//! leaf certificate fingerprint, ignoring the kdb+ credential string
+//! entirely.
+//! ```q
+//! q)h:hopen `:tcps://localhost:4321:homer:j:simpson
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use kxkdb::ipc::*;
+use rustls::RootCertStore;
+
+/// Build the server's TLS config from PEM files on disk: `server.pem` /
+/// `server.key` identify this server, `ca.pem` is the CA clients' leaf
+/// certificates must chain to for mTLS. Swap the paths for your
+/// deployment's actual certificate material.
+fn load_server_config() -> Result<Arc<rustls::ServerConfig>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(File::open("server.pem")?))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let key = rustls_pemfile::private_key(&mut BufReader::new(File::open("server.key")?))?
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no private key in server.key"))?;
+
+    let mut roots = RootCertStore::empty();
+    for ca_cert in rustls_pemfile::certs(&mut BufReader::new(File::open("ca.pem")?)) {
+        roots.add(ca_cert?)?;
+    }
+    let client_verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots)).build()?;
+
+    let config = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(client_verifier)
+        .with_single_cert(certs, key)?;
+    Ok(Arc::new(config))
+}
+
+struct PinnedFingerprintAuth {
+    allowed_fingerprints: Vec<[u8; 32]>,
+}
+
+#[async_trait]
+impl Auth for PinnedFingerprintAuth {
+    async fn authorize(&mut self, _credential: &str) -> Result<()> {
+        // Plain-credential connections are never accepted by this
+        // deployment; only `authorize_peer` (TLS) is.
+        Err(io::Error::new(io::ErrorKind::InvalidData, "mTLS required").into())
+    }
+
+    async fn authorize_peer(&mut self, _credential: &str, peer: Option<&PeerIdentity>) -> Result<()> {
+        match peer {
+            Some(peer) if self.allowed_fingerprints.contains(&peer.fingerprint_sha256) => Ok(()),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unpinned certificate").into()),
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let server_config = load_server_config()?;
+    let mut auth = PinnedFingerprintAuth {
+        allowed_fingerprints: vec![],
+    };
+    if let Ok(mut socket) = QStream::accept_auth(ConnectionMethod::TLS(server_config), "", 4321, &mut auth).await {
+        loop {
+            match socket.receive_message().await {
+                Ok((_, message)) => {
+                    println!("request: {}", message);
+                }
+                _ => {
+                    socket.shutdown().await.unwrap();
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
@@ -7,7 +7,7 @@ async fn main() -> Result<()> {
     println!("x: {}", x);
     let y = x.q_ipc_encode();
     println!("y: {:?}", y);
-    let z = K::q_ipc_decode(&y, 1_u8).await;
+    let z = K::q_ipc_decode(&y, 1_u8).await?;
     println!("z: {}", z);
     Ok(())
 }
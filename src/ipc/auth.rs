@@ -0,0 +1,43 @@
+use async_trait::async_trait;
+
+use super::{PeerIdentity, Result};
+
+/// Authorizes an incoming kdb+ handshake.
+///
+/// Implementations receive the raw credential string a client sent during
+/// the handshake (`user:password`, or just `user`) and decide whether the
+/// connection is allowed to proceed.
+#[async_trait]
+pub trait Auth: Send {
+    /// Returns `Ok(())` if `credential` is authorized, otherwise an error
+    /// (conventionally `io::ErrorKind::InvalidData`) describing why the
+    /// handshake was rejected.
+    async fn authorize(&mut self, credential: &str) -> Result<()>;
+
+    /// Like [`authorize`](Auth::authorize), but for transports that can
+    /// additionally supply the peer's verified TLS certificate. `peer` is
+    /// `Some` only when the connection arrived over
+    /// [`ConnectionMethod::TLS`](super::ConnectionMethod::TLS) and the
+    /// peer presented a certificate. The default implementation ignores
+    /// `peer` and defers to [`authorize`](Auth::authorize), so existing
+    /// implementations keep working unchanged; override it to run mTLS
+    /// deployments that authorize clients off of a pinned certificate
+    /// fingerprint, optionally still consulting `credential` for role
+    /// selection.
+    async fn authorize_peer(&mut self, credential: &str, peer: Option<&PeerIdentity>) -> Result<()> {
+        let _ = peer;
+        self.authorize(credential).await
+    }
+}
+
+/// Authorizes the PUBKEY challenge-response mechanism, mirroring the SSH
+/// `authorized_keys` lookup pattern: implementations hold an allow-list of
+/// Ed25519 public keys and accept a connection once
+/// [`QStream::accept_auth_challenge`](super::QStream::accept_auth_challenge)
+/// has already verified that the peer holds the matching private key.
+#[async_trait]
+pub trait PubKeyAuth: Send {
+    /// Returns `Ok(())` if `public_key` is a registered key, otherwise an
+    /// error (conventionally `io::ErrorKind::InvalidData`).
+    async fn authorize_key(&mut self, public_key: &[u8; 32]) -> Result<()>;
+}
@@ -0,0 +1,317 @@
+//! q's native IPC (de)compression, used transparently by
+//! [`crate::ipc::K::q_ipc_encode`] and [`crate::ipc::K::q_ipc_decode`].
+//!
+//! A compressed message keeps the normal 8-byte IPC header (with byte 2
+//! set to `1` and bytes 4..8 holding the *compressed* length), followed
+//! by a 4-byte little-endian *uncompressed* length at offset 8, followed
+//! by the compressed body starting at offset 12. The codec is a small
+//! LZ-style scheme keyed on a 256-entry hash table of 2-byte sequences,
+//! matching the layout q itself uses on the wire.
+
+use std::io;
+
+/// Build an `InvalidData` error for a malformed compressed message.
+/// `decompress` runs on bytes from a peer that, depending on the caller,
+/// may not have been authorized yet, so every malformed-input path
+/// reports an error here rather than panicking.
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Only worth compressing once the uncompressed message exceeds this many
+/// bytes, mirroring q's own threshold.
+const MIN_COMPRESS_LEN: usize = 2000;
+
+/// Compress `msg` (a full IPC message, header included) if doing so is
+/// worthwhile, i.e. `msg.len() > MIN_COMPRESS_LEN` and the compressed
+/// result is smaller than half of `msg`. Returns `None` when compression
+/// is not worth it, in which case the caller should send `msg` as-is.
+pub(crate) fn compress(msg: &[u8]) -> Option<Vec<u8>> {
+    let n = msg.len();
+    if n <= MIN_COMPRESS_LEN {
+        return None;
+    }
+
+    let mut a = [0i32; 256];
+    let mut dst = Vec::with_capacity(n / 2);
+    dst.extend_from_slice(&msg[0..8]);
+    dst.extend_from_slice(&(n as u32).to_le_bytes());
+
+    let mut s = 8usize;
+    let mut p = 8usize;
+    let mut i: u8 = 0;
+    let mut flag_pos = 0usize;
+
+    while s < n {
+        if i == 0 {
+            flag_pos = dst.len();
+            dst.push(0);
+            i = 1;
+        }
+
+        let matched = if s + 1 < n {
+            let hash = (msg[s] as usize) ^ (msg[s + 1] as usize);
+            let r = a[hash] as usize;
+            if r > 0 && r < s && msg[r] == msg[s] && msg[r + 1] == msg[s + 1] {
+                let mut extra = 0usize;
+                while s + 2 + extra < n && extra < 255 && msg[r + 2 + extra] == msg[s + 2 + extra]
+                {
+                    extra += 1;
+                }
+                dst[flag_pos] |= i;
+                dst.push(hash as u8);
+                dst.push(extra as u8);
+                s += 2 + extra;
+                true
+            } else {
+                false
+            }
+        } else {
+            false
+        };
+
+        if !matched {
+            dst.push(msg[s]);
+            s += 1;
+        }
+
+        while p < s - 1 {
+            a[(msg[p] as usize) ^ (msg[p + 1] as usize)] = p as i32;
+            p += 1;
+        }
+        if matched {
+            p = s;
+        }
+
+        i = i.wrapping_add(i);
+        if i == 0 {
+            // just overflowed past 256: next iteration starts a fresh control byte
+        }
+    }
+
+    if dst.len() < n / 2 {
+        Some(dst)
+    } else {
+        None
+    }
+}
+
+/// Reconstruct the original `n`-byte message from a compressed body.
+/// `input` is the full compressed message, i.e. the 8-byte header
+/// followed by the 4-byte uncompressed length and the compressed body
+/// (offsets 8 and 12 respectively), exactly as produced by [`compress`].
+///
+/// `n` and the contents of `input` both come straight off the wire (see
+/// [`crate::ipc::K::q_ipc_decode`]), from a peer that may not have been
+/// authorized yet, so every length used for indexing or allocation is
+/// validated rather than trusted: an `n` too small to hold the header, a
+/// back-reference that would read or write outside `output`, or a
+/// control/length byte past the end of `input` all return an error
+/// instead of panicking.
+pub(crate) fn decompress(input: &[u8], n: usize) -> io::Result<Vec<u8>> {
+    if n < 8 {
+        return Err(invalid_data(
+            "decompressed length is smaller than the 8-byte IPC header",
+        ));
+    }
+    if n > super::MAX_MESSAGE_LEN {
+        return Err(invalid_data("implausible decompressed length"));
+    }
+    if input.len() < 8 {
+        return Err(invalid_data("compressed message is shorter than the IPC header"));
+    }
+
+    let mut output = vec![0u8; n];
+    output[0..8].copy_from_slice(&input[0..8]);
+
+    let mut a = [0i32; 256];
+    let mut s = 8usize;
+    let mut p = 8usize;
+    let mut d = 12usize;
+    let mut f: u8 = 0;
+    let mut i: u8 = 0;
+
+    let read_byte = |input: &[u8], d: usize| -> io::Result<u8> {
+        input
+            .get(d)
+            .copied()
+            .ok_or_else(|| invalid_data("compressed body ends mid-token"))
+    };
+    let back_ref_byte = |output: &[u8], r: usize| -> io::Result<u8> {
+        output
+            .get(r)
+            .copied()
+            .ok_or_else(|| invalid_data("back-reference points outside the decompressed buffer"))
+    };
+    let write_byte = |output: &mut [u8], s: usize, value: u8| -> io::Result<()> {
+        *output
+            .get_mut(s)
+            .ok_or_else(|| invalid_data("decompressed body overruns the declared length"))? = value;
+        Ok(())
+    };
+
+    while s < n {
+        if i == 0 {
+            f = read_byte(input, d)?;
+            d += 1;
+            i = 1;
+        }
+
+        if f & i != 0 {
+            let hash = read_byte(input, d)? as usize;
+            d += 1;
+            let mut r = a[hash] as usize;
+
+            let byte = back_ref_byte(&output, r)?;
+            write_byte(&mut output, s, byte)?;
+            s += 1;
+            r += 1;
+            let byte = back_ref_byte(&output, r)?;
+            write_byte(&mut output, s, byte)?;
+            s += 1;
+            r += 1;
+
+            let extra = read_byte(input, d)?;
+            d += 1;
+            for _ in 0..extra {
+                let byte = back_ref_byte(&output, r)?;
+                write_byte(&mut output, s, byte)?;
+                s += 1;
+                r += 1;
+            }
+            while p < s - 1 {
+                a[(output[p] as usize) ^ (output[p + 1] as usize)] = p as i32;
+                p += 1;
+            }
+            p = s;
+        } else {
+            write_byte(&mut output, s, read_byte(input, d)?)?;
+            s += 1;
+            d += 1;
+            while p < s - 1 {
+                a[(output[p] as usize) ^ (output[p + 1] as usize)] = p as i32;
+                p += 1;
+            }
+        }
+
+        i = i.wrapping_add(i);
+    }
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: the original request asked for these round-trips to be
+    // verified against a buffer captured from real q (`-18!`). This
+    // sandbox has no q binary available to produce such a fixture, so
+    // every test below instead round-trips `compress`'s own output
+    // through `decompress` (self-consistency) plus hand-built buffers
+    // for the adversarial cases. If a real `-18!` capture becomes
+    // available, it should be added here as a hard-coded byte fixture
+    // and decompressed directly, since self-consistency alone can't
+    // catch a case where this port's `compress` and `decompress` agree
+    // with each other but disagree with q's actual wire format.
+
+    /// A long, highly repetitive body compresses well below half its
+    /// original size, and round-trips back to the exact original bytes -
+    /// this is the `new_long_list` scenario the request calls out.
+    #[test]
+    fn round_trips_a_large_repetitive_message() {
+        let mut msg = vec![1u8, 1, 0, 0, 0, 0, 0, 0];
+        for value in 0i64..400 {
+            msg.extend_from_slice(&(value % 7).to_le_bytes());
+        }
+        let n = msg.len();
+        msg[4..8].copy_from_slice(&(n as u32).to_le_bytes());
+
+        let compressed = compress(&msg).expect("repetitive data well over 2000 bytes should compress");
+        assert!(compressed.len() < n / 2);
+        assert_eq!(compressed[2], 0); // caller is responsible for setting the compressed flag
+
+        let uncompressed_len = u32::from_le_bytes(compressed[8..12].try_into().unwrap()) as usize;
+        assert_eq!(uncompressed_len, n);
+        assert_eq!(decompress(&compressed, uncompressed_len).unwrap(), msg);
+    }
+
+    /// High-entropy data that doesn't compress to under half its size must
+    /// not be returned, so the caller falls back to the plain encoding.
+    #[test]
+    fn refuses_to_compress_incompressible_data() {
+        let mut msg = vec![1u8, 1, 0, 0, 0, 0, 0, 0];
+        // `i * 177 mod 256` repeats every 256 bytes, but each 2-byte pair
+        // shares its hash with a different pair (`x` and `x ^ 128` always
+        // collide) seen more recently, so the hash table never finds a match.
+        for i in 0..3000u32 {
+            msg.push((i.wrapping_mul(2654435761) % 256) as u8);
+        }
+        let n = msg.len();
+        msg[4..8].copy_from_slice(&(n as u32).to_le_bytes());
+
+        assert!(compress(&msg).is_none());
+    }
+
+    /// Short messages are never compressed, regardless of content.
+    #[test]
+    fn refuses_to_compress_short_messages() {
+        let msg = vec![1u8, 1, 0, 0, 8, 0, 0, 0];
+        assert!(compress(&msg).is_none());
+    }
+
+    /// `decompress` on its own, against a hand-built buffer with no
+    /// back-references (every bit of every control byte is `0`), so this
+    /// exercises the literal path independent of `compress`.
+    #[test]
+    fn decompresses_an_all_literal_buffer() {
+        let header = [1u8, 1, 1, 0, 0, 0, 0, 0];
+        let body = b"hello, q"; // 8 bytes: one flag bit per byte, all clear
+        let n = 8 + body.len();
+
+        let mut input = Vec::new();
+        input.extend_from_slice(&header);
+        input.extend_from_slice(&(n as u32).to_le_bytes());
+        input.push(0); // control byte: every flag bit clear, i.e. all literals
+        input.extend_from_slice(body);
+
+        let mut expected = header.to_vec();
+        expected.extend_from_slice(body);
+        assert_eq!(decompress(&input, n).unwrap(), expected);
+    }
+
+    /// An uncompressed-length field smaller than the 8-byte header used to
+    /// panic subtracting/slicing against it; a peer that claims `n < 8`
+    /// must get an error instead.
+    #[test]
+    fn rejects_a_decompressed_length_shorter_than_the_header() {
+        let input = [1u8, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(decompress(&input, 4).is_err());
+    }
+
+    /// A truncated compressed body (cut off mid-token, or with a
+    /// back-reference pointing past what's been written so far) must be
+    /// rejected rather than panicking on an out-of-bounds index.
+    #[test]
+    fn rejects_a_truncated_compressed_body() {
+        let header = [1u8, 1, 1, 0, 0, 0, 0, 0];
+        let n = 8 + 8;
+
+        let mut input = Vec::new();
+        input.extend_from_slice(&header);
+        input.extend_from_slice(&(n as u32).to_le_bytes());
+        // Control byte claims a back-reference follows, but the body ends
+        // right after it.
+        input.push(1);
+        assert!(decompress(&input, n).is_err());
+    }
+
+    /// An implausibly large uncompressed-length field (as would come from
+    /// a malicious or corrupt header) is rejected before any allocation,
+    /// rather than attempting a multi-gigabyte `Vec`.
+    #[test]
+    fn rejects_an_implausibly_large_decompressed_length() {
+        let input = [1u8, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        assert!(decompress(&input, usize::MAX / 2).is_err());
+    }
+}
@@ -0,0 +1,296 @@
+use std::fmt;
+use std::io;
+
+use super::compression;
+
+/// Build an `InvalidData` error for a malformed q IPC message body.
+/// `decode_body` runs on bytes from a peer that, depending on the
+/// caller, may not have been authorized yet, so every malformed-input
+/// path reports an error here rather than panicking.
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// q type number for a long atom, as used on the wire.
+const QTYPE_LONG_ATOM: i8 = -7;
+/// q type number for a long vector, as used on the wire.
+const QTYPE_LONG_LIST: i8 = 7;
+/// q type number for a byte vector (`4h`), as used on the wire.
+const QTYPE_BYTE_LIST: i8 = 4;
+/// q type number for a symbol atom (`-11h`), as used on the wire.
+const QTYPE_SYMBOL_ATOM: i8 = -11;
+/// q type number for a symbol vector (`11h`), as used on the wire.
+const QTYPE_SYMBOL_LIST: i8 = 11;
+
+/// A q value, tagged with its q type.
+///
+/// Only the variants needed by the examples in this crate are modelled;
+/// more q types are added as the IPC layer grows to support them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum K {
+    /// A single q long (`-7h` atom).
+    Long(i64),
+    /// A q long list (`7h` vector) together with its attribute, see
+    /// [`crate::qattribute`].
+    LongList(Vec<i64>, u8),
+    /// A q byte list (`4h` vector), used on the wire e.g. to carry a
+    /// challenge nonce or a public key/signature pair.
+    ByteList(Vec<u8>, u8),
+    /// A single q symbol (`-11h` atom).
+    Symbol(String),
+    /// A q symbol list (`11h` vector), used for SASL-style mechanism
+    /// negotiation.
+    SymbolList(Vec<String>, u8),
+}
+
+impl K {
+    /// Build a new long list with the given attribute.
+    pub fn new_long_list(list: Vec<i64>, attribute: u8) -> Self {
+        K::LongList(list, attribute)
+    }
+
+    /// Build a new long atom.
+    pub fn new_long(value: i64) -> Self {
+        K::Long(value)
+    }
+
+    /// Build a new byte list with the given attribute.
+    pub fn new_byte_list(list: Vec<u8>, attribute: u8) -> Self {
+        K::ByteList(list, attribute)
+    }
+
+    /// Build a new symbol atom.
+    pub fn new_symbol(symbol: impl Into<String>) -> Self {
+        K::Symbol(symbol.into())
+    }
+
+    /// Build a new symbol list with the given attribute.
+    pub fn new_symbol_list(list: Vec<String>, attribute: u8) -> Self {
+        K::SymbolList(list, attribute)
+    }
+
+    /// Serialize `self` into the body of a q IPC message, i.e. without the
+    /// 8-byte message header.
+    fn encode_body(&self) -> Vec<u8> {
+        match self {
+            K::Long(value) => {
+                let mut body = vec![QTYPE_LONG_ATOM as u8];
+                body.extend_from_slice(&value.to_le_bytes());
+                body
+            }
+            K::LongList(list, attribute) => {
+                let mut body = vec![QTYPE_LONG_LIST as u8, *attribute];
+                body.extend_from_slice(&(list.len() as u32).to_le_bytes());
+                for value in list {
+                    body.extend_from_slice(&value.to_le_bytes());
+                }
+                body
+            }
+            K::ByteList(list, attribute) => {
+                let mut body = vec![QTYPE_BYTE_LIST as u8, *attribute];
+                body.extend_from_slice(&(list.len() as u32).to_le_bytes());
+                body.extend_from_slice(list);
+                body
+            }
+            K::Symbol(symbol) => {
+                let mut body = vec![QTYPE_SYMBOL_ATOM as u8];
+                body.extend_from_slice(symbol.as_bytes());
+                body.push(0);
+                body
+            }
+            K::SymbolList(list, attribute) => {
+                let mut body = vec![QTYPE_SYMBOL_LIST as u8, *attribute];
+                body.extend_from_slice(&(list.len() as u32).to_le_bytes());
+                for symbol in list {
+                    body.extend_from_slice(symbol.as_bytes());
+                    body.push(0);
+                }
+                body
+            }
+        }
+    }
+
+    /// Decode a message body produced by [`K::encode_body`] (or by q
+    /// itself). `body` comes straight off the wire, from a peer that,
+    /// depending on the caller, may not have been authorized yet, so
+    /// every index and length here is validated rather than trusted: a
+    /// truncated body, an unterminated string, or a declared element
+    /// count that doesn't fit in the remaining bytes all return an error
+    /// instead of panicking or reading past the end of `body`.
+    fn decode_body(body: &[u8]) -> io::Result<Self> {
+        let qtype = *body.first().ok_or_else(|| invalid_data("empty q IPC message body"))? as i8;
+        match qtype {
+            QTYPE_LONG_ATOM => {
+                let value = i64::from_le_bytes(
+                    body.get(1..9)
+                        .ok_or_else(|| invalid_data("truncated long atom"))?
+                        .try_into()
+                        .unwrap(),
+                );
+                Ok(K::Long(value))
+            }
+            QTYPE_LONG_LIST => {
+                let attribute = *body.get(1).ok_or_else(|| invalid_data("truncated long list"))?;
+                let count = Self::decode_count(body, "long list")?;
+                let values = body
+                    .get(6..6 + count * 8)
+                    .ok_or_else(|| invalid_data("long list count exceeds message body"))?;
+                let list = values
+                    .chunks_exact(8)
+                    .map(|chunk| i64::from_le_bytes(chunk.try_into().unwrap()))
+                    .collect();
+                Ok(K::LongList(list, attribute))
+            }
+            QTYPE_BYTE_LIST => {
+                let attribute = *body.get(1).ok_or_else(|| invalid_data("truncated byte list"))?;
+                let count = Self::decode_count(body, "byte list")?;
+                let list = body
+                    .get(6..6 + count)
+                    .ok_or_else(|| invalid_data("byte list count exceeds message body"))?
+                    .to_vec();
+                Ok(K::ByteList(list, attribute))
+            }
+            QTYPE_SYMBOL_ATOM => {
+                let rest = body.get(1..).ok_or_else(|| invalid_data("truncated symbol"))?;
+                let end = rest
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or_else(|| invalid_data("unterminated symbol"))?;
+                Ok(K::Symbol(String::from_utf8_lossy(&rest[..end]).into_owned()))
+            }
+            QTYPE_SYMBOL_LIST => {
+                let attribute = *body.get(1).ok_or_else(|| invalid_data("truncated symbol list"))?;
+                let count = Self::decode_count(body, "symbol list")?;
+                let mut list = Vec::with_capacity(count);
+                let mut cursor = 6usize;
+                for _ in 0..count {
+                    let rest = body
+                        .get(cursor..)
+                        .ok_or_else(|| invalid_data("truncated symbol list"))?;
+                    let end = rest
+                        .iter()
+                        .position(|&b| b == 0)
+                        .ok_or_else(|| invalid_data("unterminated symbol in symbol list"))?;
+                    list.push(String::from_utf8_lossy(&rest[..end]).into_owned());
+                    cursor += end + 1;
+                }
+                Ok(K::SymbolList(list, attribute))
+            }
+            other => Err(invalid_data(format!("unsupported q type on the wire: {}", other))),
+        }
+    }
+
+    /// Read the 4-byte little-endian element count at `body[2..6]`,
+    /// rejecting one so large it couldn't possibly fit in the rest of
+    /// `body` before it's used to size a `Vec::with_capacity`.
+    fn decode_count(body: &[u8], what: &str) -> io::Result<usize> {
+        let count = u32::from_le_bytes(
+            body.get(2..6)
+                .ok_or_else(|| invalid_data(format!("truncated {}", what)))?
+                .try_into()
+                .unwrap(),
+        ) as usize;
+        if count > body.len() {
+            return Err(invalid_data(format!("{} count exceeds message body", what)));
+        }
+        Ok(count)
+    }
+
+    /// Encode `self` as a full q IPC message (8-byte header included),
+    /// transparently using q's native compression when the message is
+    /// large enough for it to pay off.
+    pub fn q_ipc_encode(&self) -> Vec<u8> {
+        let body = self.encode_body();
+        let mut msg = vec![1u8, 1u8, 0u8, 0u8];
+        msg.extend_from_slice(&0u32.to_le_bytes()); // length placeholder
+        msg.extend_from_slice(&body);
+        let total_len = msg.len() as u32;
+        msg[4..8].copy_from_slice(&total_len.to_le_bytes());
+
+        match compression::compress(&msg) {
+            Some(mut compressed) => {
+                compressed[2] = 1;
+                let compressed_len = compressed.len() as u32;
+                compressed[4..8].copy_from_slice(&compressed_len.to_le_bytes());
+                compressed
+            }
+            None => msg,
+        }
+    }
+
+    /// Decode a full q IPC message produced by [`K::q_ipc_encode`] (or by
+    /// q itself), transparently inflating it first if byte 2 of the
+    /// header marks it as compressed. `msg` may come from a peer that
+    /// hasn't been authorized yet, so a malformed header, compressed
+    /// payload or body is reported as an error rather than panicking.
+    pub async fn q_ipc_decode(msg: &[u8], _msg_type: u8) -> super::Result<Self> {
+        let decompressed;
+        let body = if msg.get(2) == Some(&1) {
+            let uncompressed_len = u32::from_le_bytes(
+                msg.get(8..12)
+                    .ok_or_else(|| invalid_data("truncated compressed message header"))?
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            decompressed = compression::decompress(msg, uncompressed_len)?;
+            decompressed
+                .get(8..)
+                .ok_or_else(|| invalid_data("decompressed message shorter than its header"))?
+        } else {
+            msg.get(8..)
+                .ok_or_else(|| invalid_data("message shorter than its header"))?
+        };
+        Ok(K::decode_body(body)?)
+    }
+}
+
+impl fmt::Display for K {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            K::Long(value) => write!(f, "{}", value),
+            K::LongList(list, _) => {
+                let rendered: Vec<String> = list.iter().map(|v| v.to_string()).collect();
+                write!(f, "{}", rendered.join(" "))
+            }
+            K::ByteList(list, _) => {
+                let rendered: Vec<String> = list.iter().map(|v| format!("{:02x}", v)).collect();
+                write!(f, "{}", rendered.join(""))
+            }
+            K::Symbol(symbol) => write!(f, "`{}", symbol),
+            K::SymbolList(list, _) => {
+                let rendered: Vec<String> = list.iter().map(|s| format!("`{}", s)).collect();
+                write!(f, "{}", rendered.join(""))
+            }
+        }
+    }
+}
+
+impl K {
+    /// Unwrap a byte list. Used internally by the challenge-response
+    /// handshake to read nonces/keys/signatures off the wire; returns an
+    /// `io::ErrorKind::InvalidData` error rather than panicking, since
+    /// `self` may be whatever an unauthenticated peer chose to send.
+    pub(crate) fn into_bytes(self) -> super::Result<Vec<u8>> {
+        match self {
+            K::ByteList(list, _) => Ok(list),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "expected a q byte list",
+            )
+            .into()),
+        }
+    }
+
+    /// Unwrap a symbol atom. See [`K::into_bytes`] for why this returns a
+    /// `Result` instead of panicking.
+    pub(crate) fn into_symbol(self) -> super::Result<String> {
+        match self {
+            K::Symbol(symbol) => Ok(symbol),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "expected a q symbol",
+            )
+            .into()),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+//! q IPC protocol support: serialization of [`K`] objects to and from the
+//! kdb+ wire format, and a [`QStream`] for speaking that protocol over a
+//! socket.
+
+mod compression;
+mod k;
+mod auth;
+mod password_auth;
+mod stream;
+mod tls;
+
+pub use k::K;
+pub use auth::{Auth, PubKeyAuth};
+pub use password_auth::PasswordAuth;
+pub use stream::{AuthMechanism, ConnectionMethod, QStream};
+pub use tls::PeerIdentity;
+
+/// Convenience result type used throughout the `ipc` module.
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+/// Upper bound on the size of a single q IPC message (compressed or not)
+/// that this crate is willing to allocate for, whether declared in a
+/// message header or as a compressed payload's uncompressed-length field.
+/// These fields are read off the wire before a peer has proven its
+/// identity, so without a cap a malicious length turns into a multi-GB
+/// allocation attempt rather than a clean rejection. 64 MiB is far above
+/// any message this crate's own examples produce.
+pub(crate) const MAX_MESSAGE_LEN: usize = 64 * 1024 * 1024;
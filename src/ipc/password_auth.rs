@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use async_trait::async_trait;
+
+use super::{Auth, Result};
+
+/// Fixed, arbitrary password hashed once at construction time (see
+/// [`PasswordAuth::hash_dummy_password`]) and verified against on the
+/// not-found path in [`PasswordAuth::authorize`], so that looking up an
+/// unknown user costs the same as a failed password check for a known
+/// one, rather than returning immediately and leaking which usernames
+/// exist via timing. Hashed with this store's own `argon2` parameters
+/// rather than hard-coded at default cost, so the equalization holds
+/// even when an operator configures stronger-than-default parameters
+/// via [`PasswordAuth::from_file_with_params`].
+const DUMMY_PASSWORD: &str = "correct horse battery staple";
+
+/// Argon2id-backed [`Auth`] implementation.
+///
+/// Credentials are stored as `user:phc_hash` lines in a plain text file,
+/// where `phc_hash` is an Argon2id hash in PHC format (e.g.
+/// `$argon2id$v=19$...`). On [`authorize`](Auth::authorize), the incoming
+/// `user:password` credential is split on the first `:` and the password
+/// is verified against the stored hash in constant time.
+pub struct PasswordAuth {
+    users: HashMap<String, String>,
+    argon2: Argon2<'static>,
+    dummy_hash: String,
+}
+
+impl PasswordAuth {
+    /// Load a credentials file mapping usernames to Argon2id PHC hashes,
+    /// one `user:hash` pair per line, using default Argon2 parameters.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_file_with_params(path, Argon2::default())
+    }
+
+    /// Like [`PasswordAuth::from_file`], but with caller-supplied Argon2
+    /// parameters (memory cost, iterations, parallelism).
+    pub fn from_file_with_params(path: impl AsRef<Path>, argon2: Argon2<'static>) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let mut users = HashMap::new();
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (user, hash) = line
+                .split_once(':')
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed credentials line"))?;
+            users.insert(user.to_string(), hash.to_string());
+        }
+        let dummy_hash = Self::hash_dummy_password(&argon2)?;
+        Ok(PasswordAuth { users, argon2, dummy_hash })
+    }
+
+    /// Hash [`DUMMY_PASSWORD`] with `argon2`'s configured parameters, so
+    /// the no-such-user path in [`Auth::authorize`] pays for a verify at
+    /// the same cost a real lookup would, whatever parameters this store
+    /// was constructed with.
+    fn hash_dummy_password(argon2: &Argon2<'static>) -> Result<String> {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        Ok(argon2
+            .hash_password(DUMMY_PASSWORD.as_bytes(), &salt)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+            .to_string())
+    }
+
+    /// Hash `password` for `user` with this store's Argon2 parameters and
+    /// append the resulting `user:hash` line to the credentials file at
+    /// `path`, creating it if necessary. This is the operator-facing
+    /// helper for provisioning new users.
+    pub fn add_user(&self, path: impl AsRef<Path>, user: &str, password: &str) -> Result<()> {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let hash = self
+            .argon2
+            .hash_password(password.as_bytes(), &salt)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?
+            .to_string();
+        use std::io::Write;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)?;
+        writeln!(file, "{}:{}", user, hash)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Auth for PasswordAuth {
+    async fn authorize(&mut self, credential: &str) -> Result<()> {
+        let auth_failed = || io::Error::new(io::ErrorKind::InvalidData, "authentication failed");
+
+        let (user, password) = credential.split_once(':').ok_or_else(auth_failed)?;
+        // Look up the stored hash, falling back to a fixed dummy hash for
+        // unknown users so that an unregistered username still pays for a
+        // full Argon2 verify instead of returning immediately.
+        let stored_hash = self
+            .users
+            .get(user)
+            .map(String::as_str)
+            .unwrap_or(self.dummy_hash.as_str());
+        let user_exists = self.users.contains_key(user);
+        let parsed_hash = PasswordHash::new(stored_hash).map_err(|_| auth_failed())?;
+
+        let verified = self
+            .argon2
+            .verify_password(password.as_bytes(), &parsed_hash)
+            .is_ok();
+
+        if verified && user_exists {
+            Ok(())
+        } else {
+            Err(auth_failed().into())
+        }
+    }
+}
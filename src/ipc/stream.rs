@@ -0,0 +1,380 @@
+use std::io;
+use std::sync::Arc;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use tokio::net::{TcpStream, UnixStream};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+use super::{Auth, PeerIdentity, PubKeyAuth, Result, K};
+
+/// IPC capability byte this client/server advertises during the
+/// handshake (kdb+ 3.4+'s highest capability level, covering timestamp,
+/// timespan and compression support).
+const HANDSHAKE_CAPABILITY: u8 = 3;
+
+/// Upper bound on the `user:password\0` handshake line read by
+/// [`QStream::read_handshake`], generous for any real kdb+ credential
+/// string. Read before a peer has proven its identity, so without a cap
+/// a peer that simply never sends the null terminator would make the
+/// server accumulate an unbounded buffer.
+const MAX_HANDSHAKE_LEN: usize = 2048;
+
+/// Which transport a [`QStream`] is backed by.
+#[derive(Clone)]
+pub enum ConnectionMethod {
+    /// Plain TCP.
+    TCP,
+    /// Unix domain socket.
+    UDS,
+    /// TCP wrapped in TLS, using the given server configuration for the
+    /// handshake. The peer's verified certificate (if any) is surfaced to
+    /// [`Auth::authorize_peer`] as a [`PeerIdentity`].
+    TLS(Arc<rustls::ServerConfig>),
+}
+
+/// Mechanism negotiated by [`QStream::accept_auth_challenge`] /
+/// [`QStream::connect_with_key`], SASL-style: `Plain` is the existing
+/// static-credential check, `PubKey` is the Ed25519 challenge-response
+/// handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthMechanism {
+    /// Authorize with the plaintext credential sent during the initial
+    /// handshake, as [`QStream::accept_auth`] already does.
+    Plain,
+    /// Authorize by proving ownership of an Ed25519 private key.
+    PubKey,
+}
+
+impl AuthMechanism {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuthMechanism::Plain => "PLAIN",
+            AuthMechanism::PubKey => "PUBKEY",
+        }
+    }
+
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "PLAIN" => Some(AuthMechanism::Plain),
+            "PUBKEY" => Some(AuthMechanism::PubKey),
+            _ => None,
+        }
+    }
+}
+
+/// A connection to (or from) a kdb+ process speaking the q IPC protocol.
+pub enum QStream {
+    /// TCP-backed stream.
+    Tcp(TcpStream),
+    /// Unix-domain-socket-backed stream.
+    Uds(UnixStream),
+    /// TLS-backed stream, together with the peer's identity if it
+    /// presented a certificate during the handshake.
+    Tls(Box<TlsStream<TcpStream>>, Option<PeerIdentity>),
+}
+
+impl QStream {
+    /// Connect to a kdb+ process and perform the plaintext handshake,
+    /// sending `user:password` as the credential.
+    pub async fn connect(
+        method: ConnectionMethod,
+        host: &str,
+        port: u16,
+        credential: &str,
+    ) -> Result<Self> {
+        Self::connect_with_key(method, host, port, credential, None).await
+    }
+
+    /// Like [`QStream::connect`], but additionally able to complete the
+    /// PUBKEY challenge-response mechanism offered by
+    /// [`QStream::accept_auth_challenge`]: if the server advertises
+    /// `PUBKEY` and `signing_key` is supplied, the nonce the server sends
+    /// is signed and returned together with the public key; otherwise
+    /// `PLAIN` is selected and `credential` (already sent during the
+    /// initial handshake) stands as the sole proof of identity.
+    pub async fn connect_with_key(
+        method: ConnectionMethod,
+        host: &str,
+        port: u16,
+        credential: &str,
+        signing_key: Option<&SigningKey>,
+    ) -> Result<Self> {
+        let mut stream = match method {
+            ConnectionMethod::TCP => {
+                QStream::Tcp(TcpStream::connect((host, port)).await?)
+            }
+            ConnectionMethod::UDS => {
+                QStream::Uds(UnixStream::connect(format!("/tmp/kx.{}", port)).await?)
+            }
+            ConnectionMethod::TLS(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "QStream::connect does not yet dial out over TLS; only accept_auth* does",
+                )
+                .into());
+            }
+        };
+        stream.send_handshake(credential).await?;
+
+        // A server that only implements the plain `accept_auth` handshake
+        // sends nothing further at this point, so nothing past here runs
+        // for it; only `accept_auth_challenge` servers offer mechanisms.
+        let (_, offered) = stream.receive_message().await?;
+        let offered = match offered {
+            K::SymbolList(list, _) => list,
+            _ => return Ok(stream),
+        };
+
+        let chosen = if signing_key.is_some() && offered.iter().any(|m| m == "PUBKEY") {
+            AuthMechanism::PubKey
+        } else {
+            AuthMechanism::Plain
+        };
+        stream.send_message(&K::new_symbol(chosen.as_str())).await?;
+
+        if chosen == AuthMechanism::PubKey {
+            let signing_key = signing_key.expect("PUBKEY only chosen when a signing key is set");
+            let (_, nonce) = stream.receive_message().await?;
+            let signature = signing_key.sign(&nonce.into_bytes()?);
+            let mut response = signing_key.verifying_key().to_bytes().to_vec();
+            response.extend_from_slice(&signature.to_bytes());
+            stream
+                .send_message(&K::new_byte_list(response, crate::qattribute::NONE))
+                .await?;
+        }
+
+        Ok(stream)
+    }
+
+    /// Accept a single incoming connection on `port` and authorize it with
+    /// `auth`, rejecting the connection if `auth` returns an error. When
+    /// `method` is [`ConnectionMethod::TLS`] and the peer presented a
+    /// certificate, it is surfaced to `auth` via
+    /// [`Auth::authorize_peer`].
+    pub async fn accept_auth(
+        method: ConnectionMethod,
+        host: &str,
+        port: u16,
+        auth: &mut dyn Auth,
+    ) -> Result<Self> {
+        let (stream, credential) = Self::next_handshake(method, host, port).await?;
+        let peer = stream.peer_identity();
+        auth.authorize_peer(&credential, peer).await?;
+        Ok(stream)
+    }
+
+    /// Like [`QStream::accept_auth`], but negotiates a mechanism first
+    /// (SASL-style, `PLAIN` vs `PUBKEY`) so that clients able to prove
+    /// ownership of an Ed25519 key can skip sending a password, while
+    /// plain clients keep working exactly as with [`QStream::accept_auth`].
+    /// `auth` authorizes the `PLAIN` path; `pubkey_auth` is consulted
+    /// against an allow-list of keys (mirroring SSH's `authorized_keys`)
+    /// once the `PUBKEY` path has verified the signature over the nonce.
+    pub async fn accept_auth_challenge(
+        method: ConnectionMethod,
+        host: &str,
+        port: u16,
+        auth: &mut dyn Auth,
+        pubkey_auth: &mut dyn PubKeyAuth,
+    ) -> Result<Self> {
+        let (mut stream, credential) = Self::next_handshake(method, host, port).await?;
+
+        stream
+            .send_message(&K::new_symbol_list(
+                vec!["PLAIN".to_string(), "PUBKEY".to_string()],
+                crate::qattribute::NONE,
+            ))
+            .await?;
+        let (_, chosen) = stream.receive_message().await?;
+        let chosen = chosen.into_symbol()?;
+
+        match AuthMechanism::from_str(&chosen) {
+            Some(AuthMechanism::PubKey) => {
+                let nonce: [u8; 32] = rand::random();
+                stream
+                    .send_message(&K::new_byte_list(nonce.to_vec(), crate::qattribute::NONE))
+                    .await?;
+
+                let (_, response) = stream.receive_message().await?;
+                let response = response.into_bytes()?;
+                if response.len() != 32 + 64 {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "malformed challenge response",
+                    )
+                    .into());
+                }
+                let public_key_bytes: [u8; 32] = response[0..32].try_into().unwrap();
+                let signature = Signature::from_bytes(response[32..96].try_into().unwrap());
+                let verifying_key = VerifyingKey::from_bytes(&public_key_bytes).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "malformed public key")
+                })?;
+                verifying_key.verify(&nonce, &signature).map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "signature verification failed")
+                })?;
+
+                pubkey_auth.authorize_key(&public_key_bytes).await?;
+            }
+            // Unknown mechanisms fall back to PLAIN so that older clients
+            // which only understand the static credential keep working.
+            Some(AuthMechanism::Plain) | None => {
+                let peer = stream.peer_identity();
+                auth.authorize_peer(&credential, peer).await?;
+            }
+        }
+
+        Ok(stream)
+    }
+
+    async fn next_handshake(
+        method: ConnectionMethod,
+        host: &str,
+        port: u16,
+    ) -> Result<(Self, String)> {
+        let mut stream = match method {
+            ConnectionMethod::TCP => {
+                let listener = tokio::net::TcpListener::bind((host, port)).await?;
+                let (socket, _) = listener.accept().await?;
+                QStream::Tcp(socket)
+            }
+            ConnectionMethod::UDS => {
+                let path = format!("/tmp/kx.{}", port);
+                let _ = std::fs::remove_file(&path);
+                let listener = tokio::net::UnixListener::bind(&path)?;
+                let (socket, _) = listener.accept().await?;
+                QStream::Uds(socket)
+            }
+            ConnectionMethod::TLS(server_config) => {
+                let listener = tokio::net::TcpListener::bind((host, port)).await?;
+                let (socket, _) = listener.accept().await?;
+                let tls_stream = TlsAcceptor::from(server_config).accept(socket).await?;
+                let peer = tls_stream
+                    .get_ref()
+                    .1
+                    .peer_certificates()
+                    .and_then(PeerIdentity::from_chain);
+                QStream::Tls(Box::new(tls_stream), peer)
+            }
+        };
+        let credential = stream.read_handshake().await?;
+        Ok((stream, credential))
+    }
+
+    /// The verified peer TLS certificate identity, if `self` is backed by
+    /// [`ConnectionMethod::TLS`] and the peer presented one.
+    fn peer_identity(&self) -> Option<&PeerIdentity> {
+        match self {
+            QStream::Tls(_, peer) => peer.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Send the initial kdb+ handshake: `user:password`, a byte advertising
+    /// the highest IPC capability this client speaks, and a null
+    /// terminator. The server replies with a single byte naming the
+    /// capability it accepted, or simply closes the connection to reject
+    /// the credential.
+    async fn send_handshake(&mut self, credential: &str) -> Result<()> {
+        let mut payload = Vec::with_capacity(credential.len() + 2);
+        payload.extend_from_slice(credential.as_bytes());
+        payload.push(HANDSHAKE_CAPABILITY);
+        payload.push(0);
+        self.write_all_bytes(&payload).await?;
+        self.read_exact_bytes(1).await?;
+        Ok(())
+    }
+
+    /// Read the initial kdb+ handshake sent by `send_handshake`, returning
+    /// the `user:password` credential with the trailing capability byte
+    /// stripped off, and echo back the capability this server accepts.
+    async fn read_handshake(&mut self) -> Result<String> {
+        let mut payload = Vec::new();
+        loop {
+            let byte = self.read_exact_bytes(1).await?[0];
+            if byte == 0 {
+                break;
+            }
+            if payload.len() >= MAX_HANDSHAKE_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "handshake line exceeds the maximum credential length",
+                )
+                .into());
+            }
+            payload.push(byte);
+        }
+        payload.pop(); // trailing capability byte, not part of the credential
+        self.write_all_bytes(&[HANDSHAKE_CAPABILITY]).await?;
+        Ok(String::from_utf8_lossy(&payload).into_owned())
+    }
+
+    /// Send a [`K`] value as a full q IPC message.
+    pub async fn send_message(&mut self, value: &K) -> Result<()> {
+        self.write_all_bytes(&value.q_ipc_encode()).await
+    }
+
+    /// Receive the next q IPC message, returning its message type and the
+    /// decoded [`K`] value.
+    pub async fn receive_message(&mut self) -> Result<(u8, K)> {
+        let header = self.read_exact_bytes(8).await?;
+        let msg_type = header[1];
+        let total_len = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+        // `total_len` comes straight off the wire, from a peer that may not
+        // yet have proven its identity (e.g. the mechanism-negotiation step
+        // of `accept_auth_challenge`), so it must be validated before it is
+        // used for arithmetic or allocation: anything shorter than the
+        // header itself, or implausibly large, is rejected outright.
+        if !(8..=super::MAX_MESSAGE_LEN).contains(&total_len) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("implausible q IPC message length: {}", total_len),
+            )
+            .into());
+        }
+        let rest = self.read_exact_bytes(total_len - 8).await?;
+        let mut msg = header;
+        msg.extend_from_slice(&rest);
+        let value = K::q_ipc_decode(&msg, msg_type).await?;
+        Ok((msg_type, value))
+    }
+
+    async fn write_all_bytes(&mut self, buf: &[u8]) -> Result<()> {
+        use tokio::io::AsyncWriteExt;
+        match self {
+            QStream::Tcp(stream) => stream.write_all(buf).await?,
+            QStream::Uds(stream) => stream.write_all(buf).await?,
+            QStream::Tls(stream, _) => stream.write_all(buf).await?,
+        };
+        Ok(())
+    }
+
+    async fn read_exact_bytes(&mut self, n: usize) -> Result<Vec<u8>> {
+        use tokio::io::AsyncReadExt;
+        let mut buf = vec![0u8; n];
+        match self {
+            QStream::Tcp(stream) => stream.read_exact(&mut buf).await?,
+            QStream::Uds(stream) => stream.read_exact(&mut buf).await?,
+            QStream::Tls(stream, _) => stream.read_exact(&mut buf).await?,
+        };
+        Ok(buf)
+    }
+
+    /// Close the underlying transport.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        match self {
+            QStream::Tcp(stream) => {
+                use tokio::io::AsyncWriteExt;
+                stream.shutdown().await?;
+            }
+            QStream::Uds(stream) => {
+                use tokio::io::AsyncWriteExt;
+                stream.shutdown().await?;
+            }
+            QStream::Tls(stream, _) => {
+                use tokio::io::AsyncWriteExt;
+                stream.shutdown().await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,50 @@
+use rustls::pki_types::CertificateDer;
+use sha2::{Digest, Sha256};
+
+/// Identity of a peer authenticated over TLS, surfaced to
+/// [`super::Auth::authorize_peer`] so servers can run mTLS deployments that
+/// authorize clients off of a pinned certificate fingerprint rather than
+/// (or in addition to) the kdb+ credential string.
+#[derive(Debug, Clone)]
+pub struct PeerIdentity {
+    /// DER-encoded certificate chain the peer presented, leaf first.
+    pub certificate_chain: Vec<Vec<u8>>,
+    /// Subject of the leaf certificate, as a human-readable string; empty
+    /// if the certificate could not be parsed.
+    pub subject: String,
+    /// Subject Alternative Names of the leaf certificate (DNS names,
+    /// emails, IPs, ...), as human-readable strings; empty if the
+    /// certificate has no SAN extension or could not be parsed.
+    pub sans: Vec<String>,
+    /// SHA-256 fingerprint of the leaf certificate's DER encoding.
+    pub fingerprint_sha256: [u8; 32],
+}
+
+impl PeerIdentity {
+    /// Build a [`PeerIdentity`] from the verified certificate chain rustls
+    /// hands back after a successful handshake. Returns `None` if the peer
+    /// presented no certificate (e.g. the client authenticated via
+    /// credential only).
+    pub(crate) fn from_chain(chain: &[CertificateDer<'static>]) -> Option<Self> {
+        let leaf = chain.first()?;
+        let fingerprint_sha256: [u8; 32] = Sha256::digest(leaf.as_ref()).into();
+        let parsed = x509_parser::parse_x509_certificate(leaf.as_ref())
+            .ok()
+            .map(|(_, cert)| cert);
+        let subject = parsed
+            .as_ref()
+            .map(|cert| cert.subject().to_string())
+            .unwrap_or_default();
+        let sans = parsed
+            .as_ref()
+            .and_then(|cert| cert.subject_alternative_name().ok().flatten())
+            .map(|ext| ext.value.general_names.iter().map(|name| name.to_string()).collect())
+            .unwrap_or_default();
+        Some(PeerIdentity {
+            certificate_chain: chain.iter().map(|c| c.as_ref().to_vec()).collect(),
+            subject,
+            sans,
+            fingerprint_sha256,
+        })
+    }
+}
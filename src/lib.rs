@@ -0,0 +1,7 @@
+//! # kxkdb
+//!
+//! An async client library for kdb+, providing q IPC serialization and
+//! a tokio-based connection stream for talking to a kdb+ process.
+
+pub mod ipc;
+pub mod qattribute;
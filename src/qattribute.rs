@@ -0,0 +1,13 @@
+//! Attributes that can be attached to a q list, mirroring the attribute
+//! byte found immediately after the type byte on the wire.
+
+/// No attribute.
+pub const NONE: u8 = 0;
+/// The list is sorted in ascending order.
+pub const SORTED: u8 = 1;
+/// Every element of the list is unique.
+pub const UNIQUE: u8 = 2;
+/// The list is partitioned, i.e. equal values are contiguous.
+pub const PARTED: u8 = 3;
+/// The list is grouped.
+pub const GROUPED: u8 = 4;